@@ -1,5 +1,6 @@
 mod compositor;
-mod xdg_shell;
+pub(crate) mod wl_shell;
+pub(crate) mod xdg_shell;
 
 use crate::State;
 
@@ -7,8 +8,10 @@ use crate::State;
 // Wl Seat
 //
 
-use smithay::wayland::data_device::{ClientDndGrabHandler, DataDeviceHandler, ServerDndGrabHandler};
-use smithay::wayland::seat::{SeatHandler, SeatState};
+use smithay::reexports::wayland_server::protocol::wl_data_source::WlDataSource;
+use smithay::wayland::data_device::{with_source_metadata, ClientDndGrabHandler, DataDeviceHandler, ServerDndGrabHandler};
+use smithay::wayland::seat::{Seat, SeatHandler, SeatState};
+use smithay::xwayland::xwm::SelectionTarget;
 use smithay::{delegate_data_device, delegate_output, delegate_seat};
 
 impl SeatHandler for State {
@@ -27,6 +30,19 @@ impl DataDeviceHandler for State {
     fn data_device_state(&self) -> &smithay::wayland::data_device::DataDeviceState {
         &self.data_device_state
     }
+
+    // Mirror of `XwmHandler::new_selection`: whenever a Wayland client takes
+    // (or gives up) the clipboard, tell the xwm so X11 clients learn a
+    // selection exists and can request its contents through `send_selection`.
+    fn new_selection(&mut self, source: Option<WlDataSource>, _seat: Seat<State>) {
+        let Some(xwm) = self.xwm.as_mut() else { return };
+
+        let mime_types = source
+            .and_then(|source| with_source_metadata(&source, |meta| meta.mime_types.clone()).ok());
+        if let Err(err) = xwm.set_selection(SelectionTarget::Clipboard, mime_types) {
+            slog::warn!(self.log, "failed to advertise wayland selection to X11: {}", err);
+        }
+    }
 }
 
 impl ClientDndGrabHandler for State {}
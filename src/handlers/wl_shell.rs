@@ -0,0 +1,143 @@
+use std::sync::Mutex;
+
+use smithay::{
+    delegate_shell,
+    desktop::{Kind, Window, WindowSurfaceType},
+    reexports::{
+        wayland_protocols::wl_shell::server::wl_shell_surface::Resize,
+        wayland_server::{
+            protocol::{wl_seat, wl_surface::WlSurface},
+            DisplayHandle,
+        },
+    },
+    utils::Rectangle,
+    wayland::{
+        compositor::with_states,
+        seat::{Focus, Seat},
+        shell::legacy::{ShellHandler, ShellRequest, ShellState, ShellSurfaceKind},
+        Serial, SERIAL_COUNTER,
+    },
+};
+
+use crate::{
+    grabs::{MoveSurfaceGrab, ResizeSurfaceGrab},
+    handlers::xdg_shell::check_grab,
+    State,
+};
+
+impl ShellHandler for State {
+    fn shell_state(&mut self) -> &mut ShellState {
+        &mut self.wl_shell_state
+    }
+
+    fn request(&mut self, _dh: &DisplayHandle, request: ShellRequest) {
+        match request {
+            ShellRequest::SetKind {
+                surface,
+                kind: ShellSurfaceKind::Toplevel,
+            } => {
+                let window = Window::new(Kind::Wl(surface));
+
+                // Cascade, same as xdg_shell::new_toplevel, so the two shell
+                // protocols place and focus new windows identically.
+                let step = 24;
+                let offset = (self.space.windows().count() as i32 % 10) * step;
+                self.space.map_window(&window, (offset, offset), None, false);
+
+                let serial = SERIAL_COUNTER.next_serial();
+                self.focus_window(&window, serial);
+            }
+            ShellRequest::SetKind { .. } => {
+                // Popups and transients are left unmapped until something in
+                // this tree actually needs them.
+            }
+            ShellRequest::Move { surface, seat, serial } => self.wl_shell_move(&surface.wl_surface().clone(), &seat, serial),
+            ShellRequest::Resize {
+                surface,
+                seat,
+                serial,
+                edges,
+            } => self.wl_shell_resize(&surface.wl_surface().clone(), &seat, serial, edges),
+            ShellRequest::SetTitle { surface, title } => {
+                with_states(surface.wl_surface(), |states| {
+                    states
+                        .data_map
+                        .insert_if_missing(|| Mutex::new(WlShellSurfaceTitle::default()));
+                    *states
+                        .data_map
+                        .get::<Mutex<WlShellSurfaceTitle>>()
+                        .unwrap()
+                        .lock()
+                        .unwrap() = WlShellSurfaceTitle(title);
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+delegate_shell!(State);
+
+/// The title a legacy `wl_shell_surface` last set via `set_title`, kept on the
+/// surface's `data_map` the same way xdg_shell's title lives on
+/// `XdgToplevelSurfaceRoleAttributes`.
+#[derive(Default)]
+pub(crate) struct WlShellSurfaceTitle(pub String);
+
+impl State {
+    fn window_for_wl_surface(&self, surface: &WlSurface) -> Option<Window> {
+        self.space
+            .window_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+            .cloned()
+    }
+
+    fn wl_shell_move(&mut self, wl_surface: &WlSurface, seat: &wl_seat::WlSeat, serial: Serial) {
+        let seat = match Seat::from_resource(seat) {
+            Some(seat) => seat,
+            None => return,
+        };
+
+        if let Some(start_data) = check_grab(&seat, wl_surface, serial) {
+            let window = match self.window_for_wl_surface(wl_surface) {
+                Some(window) => window,
+                None => return,
+            };
+            let initial_window_location = self.space.window_location(&window).unwrap();
+
+            let pointer = seat.get_pointer().unwrap();
+            let grab = MoveSurfaceGrab {
+                start_data,
+                window,
+                initial_window_location,
+            };
+
+            pointer.set_grab(grab, serial, Focus::Clear);
+        }
+    }
+
+    fn wl_shell_resize(&mut self, wl_surface: &WlSurface, seat: &wl_seat::WlSeat, serial: Serial, edges: Resize) {
+        let seat = match Seat::from_resource(seat) {
+            Some(seat) => seat,
+            None => return,
+        };
+
+        if let Some(start_data) = check_grab(&seat, wl_surface, serial) {
+            let window = match self.window_for_wl_surface(wl_surface) {
+                Some(window) => window,
+                None => return,
+            };
+            let initial_window_location = self.space.window_location(&window).unwrap();
+            let initial_window_size = window.geometry().size;
+
+            let pointer = seat.get_pointer().unwrap();
+            let grab = ResizeSurfaceGrab::start(
+                start_data,
+                window,
+                edges.into(),
+                Rectangle::from_loc_and_size(initial_window_location, initial_window_size),
+            );
+
+            pointer.set_grab(grab, serial, Focus::Clear);
+        }
+    }
+}
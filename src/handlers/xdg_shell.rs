@@ -6,11 +6,12 @@ use smithay::{
     reexports::{
         wayland_protocols::xdg::shell::server::xdg_toplevel,
         wayland_server::{
-            protocol::{wl_seat, wl_surface::WlSurface},
+            protocol::{wl_output, wl_seat, wl_surface::WlSurface},
             DisplayHandle, Resource,
         },
     },
     utils::Rectangle,
+    wayland::output::Output,
     wayland::{
         compositor::with_states,
         seat::{Focus, PointerGrabStartData, Seat},
@@ -18,7 +19,7 @@ use smithay::{
             PopupSurface, PositionerState, ToplevelSurface, XdgShellHandler, XdgShellState,
             XdgToplevelSurfaceRoleAttributes,
         },
-        Serial,
+        Serial, SERIAL_COUNTER,
     },
 };
 
@@ -34,7 +35,14 @@ impl XdgShellHandler for State {
 
     fn new_toplevel(&mut self, _dh: &DisplayHandle, surface: ToplevelSurface) {
         let window = Window::new(Kind::Xdg(surface));
-        self.space.map_window(&window, (0, 0), None, false);
+
+        // Cascade new toplevels instead of stacking them all at the origin.
+        let step = 24;
+        let offset = (self.space.windows().count() as i32 % 10) * step;
+        self.space.map_window(&window, (offset, offset), None, false);
+
+        let serial = SERIAL_COUNTER.next_serial();
+        self.focus_window(&window, serial);
     }
     fn new_popup(&mut self, _dh: &DisplayHandle, _surface: PopupSurface, _positioner: PositionerState) {}
 
@@ -112,12 +120,190 @@ impl XdgShellHandler for State {
     fn grab(&mut self, _dh: &DisplayHandle, _surface: PopupSurface, _seat: wl_seat::WlSeat, _serial: Serial) {
         // TODO popup grabs
     }
+
+    fn maximize_request(&mut self, _dh: &DisplayHandle, surface: ToplevelSurface) {
+        let wl_surface = surface.wl_surface().clone();
+        let window = match self.space.window_for_surface(&wl_surface, WindowSurfaceType::TOPLEVEL) {
+            Some(window) => window.clone(),
+            None => return,
+        };
+        let output = match self.output_for_window(&window) {
+            Some(output) => output,
+            None => return,
+        };
+        let geometry = self.space.output_geometry(&output).unwrap();
+
+        // A window that's already fullscreened is showing the fullscreen
+        // rect, not its true pre-fullscreen size - hand that saved rect off
+        // to the maximize slot instead of re-capturing current geometry.
+        if surface.current_state().states.contains(xdg_toplevel::State::Fullscreen) {
+            if let Some(rect) = take_pre_state(&wl_surface, SavedRectSlot::Fullscreen) {
+                save_pre_state(&wl_surface, rect, SavedRectSlot::Maximize);
+            }
+        } else {
+            save_pre_state(
+                &wl_surface,
+                Rectangle::from_loc_and_size(self.space.window_location(&window).unwrap(), window.geometry().size),
+                SavedRectSlot::Maximize,
+            );
+        }
+
+        surface.with_pending_state(|state| {
+            state.states.set(xdg_toplevel::State::Maximized);
+            state.states.unset(xdg_toplevel::State::Fullscreen);
+            state.size = Some(geometry.size);
+        });
+        surface.send_configure();
+
+        self.space.map_window(&window, geometry.loc, None, false);
+    }
+
+    fn unmaximize_request(&mut self, _dh: &DisplayHandle, surface: ToplevelSurface) {
+        let wl_surface = surface.wl_surface().clone();
+        let window = match self.space.window_for_surface(&wl_surface, WindowSurfaceType::TOPLEVEL) {
+            Some(window) => window.clone(),
+            None => return,
+        };
+        let restored = take_pre_state(&wl_surface, SavedRectSlot::Maximize);
+
+        surface.with_pending_state(|state| {
+            state.states.unset(xdg_toplevel::State::Maximized);
+            state.size = restored.map(|rect| rect.size);
+        });
+        surface.send_configure();
+
+        if let Some(rect) = restored {
+            self.space.map_window(&window, rect.loc, None, false);
+        }
+    }
+
+    fn fullscreen_request(
+        &mut self,
+        _dh: &DisplayHandle,
+        surface: ToplevelSurface,
+        wl_output: Option<wl_output::WlOutput>,
+    ) {
+        let wl_surface = surface.wl_surface().clone();
+        let window = match self.space.window_for_surface(&wl_surface, WindowSurfaceType::TOPLEVEL) {
+            Some(window) => window.clone(),
+            None => return,
+        };
+        let output = match wl_output
+            .as_ref()
+            .and_then(Output::from_resource)
+            .or_else(|| self.output_for_window(&window))
+        {
+            Some(output) => output,
+            None => return,
+        };
+        let geometry = self.space.output_geometry(&output).unwrap();
+
+        // Mirror of the maximize path above: hand off the maximize rect
+        // rather than re-capturing the already-maximized geometry as if it
+        // were the window's true original size.
+        if surface.current_state().states.contains(xdg_toplevel::State::Maximized) {
+            if let Some(rect) = take_pre_state(&wl_surface, SavedRectSlot::Maximize) {
+                save_pre_state(&wl_surface, rect, SavedRectSlot::Fullscreen);
+            }
+        } else {
+            save_pre_state(
+                &wl_surface,
+                Rectangle::from_loc_and_size(self.space.window_location(&window).unwrap(), window.geometry().size),
+                SavedRectSlot::Fullscreen,
+            );
+        }
+
+        surface.with_pending_state(|state| {
+            state.states.set(xdg_toplevel::State::Fullscreen);
+            state.states.unset(xdg_toplevel::State::Maximized);
+            state.size = Some(geometry.size);
+        });
+        surface.send_configure();
+
+        self.space.map_window(&window, geometry.loc, None, false);
+    }
+
+    fn unfullscreen_request(&mut self, _dh: &DisplayHandle, surface: ToplevelSurface) {
+        let wl_surface = surface.wl_surface().clone();
+        let window = match self.space.window_for_surface(&wl_surface, WindowSurfaceType::TOPLEVEL) {
+            Some(window) => window.clone(),
+            None => return,
+        };
+        let restored = take_pre_state(&wl_surface, SavedRectSlot::Fullscreen);
+
+        surface.with_pending_state(|state| {
+            state.states.unset(xdg_toplevel::State::Fullscreen);
+            state.size = restored.map(|rect| rect.size);
+        });
+        surface.send_configure();
+
+        if let Some(rect) = restored {
+            self.space.map_window(&window, rect.loc, None, false);
+        }
+    }
+}
+
+impl State {
+    fn output_for_window(&self, window: &Window) -> Option<Output> {
+        self.space
+            .output_for_window(window)
+            .or_else(|| self.space.outputs().next())
+            .cloned()
+    }
+}
+
+/// Which request a saved pre-state rectangle belongs to. Maximize and
+/// fullscreen each have their own slot; when a window goes from one state
+/// straight to the other, the saved rect moves to the new slot, so unsetting
+/// restores the geometry from before the first of the two requests.
+#[derive(Clone, Copy)]
+enum SavedRectSlot {
+    Maximize,
+    Fullscreen,
+}
+
+/// The geometry a toplevel had before it was maximized and/or fullscreened,
+/// kept on the surface's `data_map` so `unmaximize`/`unfullscreen` can restore it.
+#[derive(Default)]
+struct PreMaximizeState {
+    maximize_rect: Option<Rectangle<i32, smithay::utils::Logical>>,
+    fullscreen_rect: Option<Rectangle<i32, smithay::utils::Logical>>,
+}
+
+fn save_pre_state(surface: &WlSurface, rect: Rectangle<i32, smithay::utils::Logical>, slot: SavedRectSlot) {
+    with_states(surface, |states| {
+        states
+            .data_map
+            .insert_if_missing(|| Mutex::new(PreMaximizeState::default()));
+        let mut saved = states
+            .data_map
+            .get::<Mutex<PreMaximizeState>>()
+            .unwrap()
+            .lock()
+            .unwrap();
+        match slot {
+            SavedRectSlot::Maximize => saved.maximize_rect.get_or_insert(rect),
+            SavedRectSlot::Fullscreen => saved.fullscreen_rect.get_or_insert(rect),
+        };
+    });
+}
+
+fn take_pre_state(surface: &WlSurface, slot: SavedRectSlot) -> Option<Rectangle<i32, smithay::utils::Logical>> {
+    with_states(surface, |states| {
+        states.data_map.get::<Mutex<PreMaximizeState>>().and_then(|saved| {
+            let mut saved = saved.lock().unwrap();
+            match slot {
+                SavedRectSlot::Maximize => saved.maximize_rect.take(),
+                SavedRectSlot::Fullscreen => saved.fullscreen_rect.take(),
+            }
+        })
+    })
 }
 
 // Xdg Shell
 delegate_xdg_shell!(State);
 
-fn check_grab(seat: &Seat<State>, surface: &WlSurface, serial: Serial) -> Option<PointerGrabStartData> {
+pub(crate) fn check_grab(seat: &Seat<State>, surface: &WlSurface, serial: Serial) -> Option<PointerGrabStartData> {
     let pointer = seat.get_pointer()?;
 
     // Check that this surface has a click grab.
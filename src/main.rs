@@ -5,14 +5,20 @@ use std::{ffi::OsString, sync::Arc};
 
 use slog::Logger;
 use smithay::{
-    desktop::{Space, WindowSurfaceType},
+    backend::input::{
+        Axis, AxisSource, ButtonState, Event, InputBackend, InputEvent, KeyState,
+        KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionAbsoluteEvent,
+        PointerMotionEvent,
+    },
+    desktop::{Kind, Space, Window, WindowSurfaceType},
     reexports::{
+        wayland_protocols::xdg::shell::server::xdg_toplevel,
         calloop::{
-            generic::Generic, 
-            EventLoop, 
-            Interest, 
-            LoopSignal, 
-            Mode, 
+            generic::Generic,
+            EventLoop,
+            Interest,
+            LoopSignal,
+            Mode,
             PostAction,
             timer::{TimeoutAction, Timer},
         },
@@ -22,7 +28,7 @@ use smithay::{
                 wl_surface::WlSurface,
                 wl_output,
             },
-            Display,
+            Display, DisplayHandle,
         },
     },
     backend::{
@@ -35,18 +41,23 @@ use smithay::{
         compositor::CompositorState,
         data_device::DataDeviceState,
         output::OutputManagerState,
-        seat::{Seat, SeatState},
-        shell::xdg::XdgShellState,
+        seat::{keysyms, AxisFrame, FilterResult, Seat, SeatState, XkbConfig},
+        shell::{legacy::ShellState as WlShellState, xdg::XdgShellState},
         shm::ShmState,
         socket::ListeningSocketSource,
     },
     wayland::output::{Mode as OutputMode, Output, PhysicalProperties},
+    wayland::Serial,
+    wayland::SERIAL_COUNTER,
+    xwayland::{X11Wm, XWayland},
 };
 
 use std::time::Duration;
 
 mod handlers;
 mod grabs;
+mod udev;
+mod xwayland;
 
 pub struct State {
     pub space: Space,
@@ -58,10 +69,18 @@ pub struct State {
 
     pub compositor_state: CompositorState,
     pub xdg_shell_state: XdgShellState,
+    pub wl_shell_state: WlShellState,
     pub shm_state: ShmState,
     pub output_manager_state: OutputManagerState,
     pub seat_state: SeatState<State>,
-    pub data_device_state: DataDeviceState, 
+    pub data_device_state: DataDeviceState,
+
+    pub seat: Seat<State>,
+    pub pointer_location: Point<f64, Logical>,
+
+    pub display_handle: DisplayHandle,
+    pub xwayland: Option<XWayland>,
+    pub xwm: Option<X11Wm>,
 }
 
 pub struct CalloopData {
@@ -69,6 +88,13 @@ pub struct CalloopData {
     display: Display<State>,
 }
 
+/// Compositor-level shortcuts intercepted by the keyboard filter before a key
+/// would otherwise be forwarded to the focused client.
+enum CompositorKeyAction {
+    Quit,
+    CycleFocus,
+}
+
 impl State {
     pub fn new(event_loop: &mut EventLoop<CalloopData>, display: &mut Display<Self>, log: Logger) -> Self {
         let dh = display.handle();
@@ -76,11 +102,17 @@ impl State {
 
         let compositor_state = CompositorState::new::<Self, _>(&dh, log.clone());
         let xdg_shell_state = XdgShellState::new::<Self, _>(&dh, log.clone());
+        let wl_shell_state = WlShellState::new::<Self, _>(&dh, log.clone());
         let shm_state = ShmState::new::<Self, _>(&dh, vec![], log.clone());
         let output_manager_state = OutputManagerState::new_with_xdg_output::<Self>(&dh);
-        let seat_state = SeatState::new();
+        let mut seat_state = SeatState::new();
         let data_device_state = DataDeviceState::new::<Self, _>(&dh, log.clone());
 
+        let mut seat = seat_state.new_wl_seat(&dh, "seat0", log.clone());
+        seat.add_keyboard(XkbConfig::default(), 200, 25)
+            .expect("Failed to initialize the keyboard");
+        seat.add_pointer();
+
         let space = Space::new(log.clone());
         let loop_signal = event_loop.get_signal();
 
@@ -96,11 +128,201 @@ impl State {
 
             compositor_state,
             xdg_shell_state,
+            wl_shell_state,
             shm_state,
             output_manager_state,
             seat_state,
             data_device_state,
+
+            seat,
+            pointer_location: (0.0, 0.0).into(),
+
+            display_handle: dh,
+            xwayland: None,
+            xwm: None,
+        }
+    }
+
+    fn process_input_event<B: InputBackend>(&mut self, event: InputEvent<B>, output: &Output) {
+        match event {
+            InputEvent::Keyboard { event, .. } => {
+                let keycode = event.key_code();
+                let key_state = event.state();
+                let serial = SERIAL_COUNTER.next_serial();
+                let time = Event::time(&event);
+
+                let keyboard = self.seat.get_keyboard().unwrap();
+                let action = keyboard.input::<CompositorKeyAction, _>(
+                    keycode,
+                    key_state,
+                    serial,
+                    time,
+                    |modifiers, handle| {
+                        // Ctrl+Alt+Escape is reserved for the compositor and never forwarded
+                        // to clients, mirroring the classic "kill the session" VT binding.
+                        if modifiers.ctrl && modifiers.alt && handle.modified_sym() == keysyms::KEY_Escape {
+                            return FilterResult::Intercept(CompositorKeyAction::Quit);
+                        }
+
+                        if modifiers.alt
+                            && handle.modified_sym() == keysyms::KEY_Tab
+                            && key_state == KeyState::Pressed
+                        {
+                            return FilterResult::Intercept(CompositorKeyAction::CycleFocus);
+                        }
+
+                        FilterResult::Forward
+                    },
+                );
+
+                match action {
+                    Some(CompositorKeyAction::Quit) => self.loop_signal.stop(),
+                    Some(CompositorKeyAction::CycleFocus) => self.cycle_focus(),
+                    None => {}
+                }
+            }
+            InputEvent::PointerMotionAbsolute { event, .. } => {
+                let output_geo = self.space.output_geometry(output).unwrap();
+                let pos = event.position_transformed(output_geo.size) + output_geo.loc.to_f64();
+                self.pointer_location = pos;
+
+                let serial = SERIAL_COUNTER.next_serial();
+                let under = self.space.surface_under(pos, WindowSurfaceType::all());
+
+                let pointer = self.seat.get_pointer().unwrap();
+                pointer.motion(pos, under, serial, event.time());
+            }
+            InputEvent::PointerMotion { event, .. } => {
+                let output_geo = self.space.output_geometry(output).unwrap();
+                let pos = self.clamp_pointer_location(self.pointer_location + event.delta(), output_geo);
+                self.pointer_location = pos;
+
+                let serial = SERIAL_COUNTER.next_serial();
+                let under = self.space.surface_under(pos, WindowSurfaceType::all());
+
+                let pointer = self.seat.get_pointer().unwrap();
+                pointer.motion(pos, under, serial, event.time());
+            }
+            InputEvent::PointerButton { event, .. } => {
+                let serial = SERIAL_COUNTER.next_serial();
+                let button = event.button_code();
+                let button_state = event.state();
+
+                let pointer = self.seat.get_pointer().unwrap();
+                let keyboard = self.seat.get_keyboard().unwrap();
+
+                if button_state == ButtonState::Pressed && !pointer.is_grabbed() {
+                    if let Some(window) = self.space.window_under(self.pointer_location).cloned() {
+                        self.focus_window(&window, serial);
+                    } else {
+                        keyboard.set_focus(None, serial);
+                    }
+                }
+
+                pointer.button(button, button_state, serial, event.time());
+            }
+            InputEvent::PointerAxis { event, .. } => {
+                let source = event.source();
+
+                let horizontal_amount = event
+                    .amount(Axis::Horizontal)
+                    .unwrap_or_else(|| event.amount_discrete(Axis::Horizontal).unwrap_or(0.0) * 3.0);
+                let vertical_amount = event
+                    .amount(Axis::Vertical)
+                    .unwrap_or_else(|| event.amount_discrete(Axis::Vertical).unwrap_or(0.0) * 3.0);
+                let horizontal_amount_discrete = event.amount_discrete(Axis::Horizontal);
+                let vertical_amount_discrete = event.amount_discrete(Axis::Vertical);
+
+                let mut frame = AxisFrame::new(event.time()).source(source);
+                if horizontal_amount != 0.0 {
+                    frame = frame.value(Axis::Horizontal, horizontal_amount);
+                    if let Some(discrete) = horizontal_amount_discrete {
+                        frame = frame.discrete(Axis::Horizontal, discrete as i32);
+                    }
+                } else if source == AxisSource::Finger {
+                    frame = frame.stop(Axis::Horizontal);
+                }
+                if vertical_amount != 0.0 {
+                    frame = frame.value(Axis::Vertical, vertical_amount);
+                    if let Some(discrete) = vertical_amount_discrete {
+                        frame = frame.discrete(Axis::Vertical, discrete as i32);
+                    }
+                } else if source == AxisSource::Finger {
+                    frame = frame.stop(Axis::Vertical);
+                }
+
+                self.seat.get_pointer().unwrap().axis(frame);
+            }
+            _ => {}
+        }
+    }
+
+    /// Raises `window` to the top of the stack and gives it keyboard focus,
+    /// marking it (and only it) as the xdg-activated toplevel.
+    pub(crate) fn focus_window(&mut self, window: &Window, serial: Serial) {
+        self.space.raise_window(window, true);
+
+        for mapped in self.space.windows() {
+            if let Kind::Xdg(toplevel) = mapped.toplevel() {
+                let activated = mapped == window;
+                toplevel.with_pending_state(|state| {
+                    state.states.set(xdg_toplevel::State::Activated);
+                    if !activated {
+                        state.states.unset(xdg_toplevel::State::Activated);
+                    }
+                });
+                toplevel.send_configure();
+            }
         }
+
+        let keyboard = self.seat.get_keyboard().unwrap();
+        keyboard.set_focus(Some(window.toplevel().wl_surface()), serial);
+    }
+
+    /// Alt+Tab: focuses the window currently at the bottom of the stack, which
+    /// `focus_window` then raises to the top — repeated presses cycle through
+    /// every mapped window.
+    fn cycle_focus(&mut self) {
+        let windows: Vec<Window> = self.space.windows().cloned().collect();
+        if windows.len() < 2 {
+            return;
+        }
+
+        let next = windows.first().unwrap().clone();
+        let serial = SERIAL_COUNTER.next_serial();
+        self.focus_window(&next, serial);
+    }
+
+    /// The output the pointer currently sits over, for backends (e.g. libinput
+    /// on a bare TTY) that hand us a raw event with no output of its own.
+    /// Falls back to the first mapped output so a pointer that's somehow
+    /// drifted outside every output's geometry still gets events delivered.
+    pub(crate) fn output_under_pointer(&self) -> Option<Output> {
+        self.space
+            .outputs()
+            .find(|output| {
+                self.space
+                    .output_geometry(output)
+                    .map_or(false, |geo| geo.to_f64().contains(self.pointer_location))
+            })
+            .or_else(|| self.space.outputs().next())
+            .cloned()
+    }
+
+    /// Keeps the pointer within the bounds of the output it moved on, for backends
+    /// (e.g. libinput on a bare TTY) that only report relative motion.
+    fn clamp_pointer_location(
+        &self,
+        pos: Point<f64, Logical>,
+        output_geo: Rectangle<i32, Logical>,
+    ) -> Point<f64, Logical> {
+        let max_x = (output_geo.loc.x + output_geo.size.w) as f64;
+        let max_y = (output_geo.loc.y + output_geo.size.h) as f64;
+
+        Point::from((
+            pos.x.clamp(output_geo.loc.x as f64, max_x),
+            pos.y.clamp(output_geo.loc.y as f64, max_y),
+        ))
     }
 
      fn init_wayland_listener(
@@ -227,8 +449,8 @@ pub fn winit_dispatch(
             );
         }
         WinitEvent::Input(event) => {
-            println!("Input event: {:?}", event);
-        },
+            state.process_input_event(event, output);
+        }
         _ => (),
     });
 
@@ -282,9 +504,15 @@ fn main() {
 
     let mut data = CalloopData { state, display };
 
-    winit_backend(&mut event_loop, &mut data, log).unwrap();
+    // Nested inside another compositor we speak winit; on a bare TTY we drive
+    // the hardware ourselves through DRM/GBM/libinput.
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() || std::env::var_os("DISPLAY").is_some() {
+        winit_backend(&mut event_loop, &mut data, log.clone()).unwrap();
+    } else {
+        udev::udev_backend(&mut event_loop, &mut data, log.clone()).unwrap();
+    }
 
-    std::process::Command::new("alacritty").spawn().ok();
+    data.state.start_xwayland(&event_loop.handle(), log);
 
     event_loop.run(None, &mut data, move |_| {
         // Episk is running
@@ -0,0 +1,366 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    rc::Rc,
+};
+
+use slog::Logger;
+use smithay::{
+    backend::{
+        drm::{DrmDevice, DrmEvent, GbmBufferedSurface},
+        egl::{EGLContext, EGLDisplay},
+        libinput::{LibinputInputBackend, LibinputSessionInterface},
+        renderer::{gles2::Gles2Renderer, Bind},
+        session::{auto::AutoSession, Session, Signal as SessionSignal},
+        udev::{UdevBackend, UdevEvent},
+    },
+    desktop::space::SurfaceTree,
+    reexports::{
+        calloop::{EventLoop, LoopHandle},
+        drm::control::{connector::State as ConnectorState, crtc, Device as ControlDevice, ModeTypeFlags},
+        gbm::Device as GbmDevice,
+        input::Libinput,
+        nix::fcntl::OFlag,
+        wayland_server::{protocol::wl_output, DisplayHandle},
+    },
+    wayland::output::{Mode as OutputMode, Output, PhysicalProperties},
+};
+
+use crate::{CalloopData, State};
+
+type DeviceId = u64;
+type Surface = GbmBufferedSurface<GbmDevice<std::fs::File>, std::fs::File>;
+
+/// A mapped connector: the DRM surface we scan out of, and the `Output` global
+/// clients see for it.
+struct OutputSurface {
+    surface: Surface,
+    output: Output,
+}
+
+struct Device {
+    drm: DrmDevice<std::fs::File>,
+    gbm: GbmDevice<std::fs::File>,
+    renderer: Gles2Renderer,
+    surfaces: HashMap<crtc::Handle, OutputSurface>,
+}
+
+type DeviceMap = Rc<RefCell<HashMap<DeviceId, Device>>>;
+/// Set while the `PauseSession` signal is in effect and shared with every
+/// vblank source, so vblank events still queued after the pause do not render
+/// against a device whose DRM master has been revoked.
+type PausedFlag = Rc<Cell<bool>>;
+
+/// Runs episk directly on a Linux VT: DRM/GBM for scanout, libseat for the
+/// session, libinput for input. Selected by `main` when no nested Wayland or
+/// X11 display is available.
+pub fn udev_backend(
+    event_loop: &mut EventLoop<CalloopData>,
+    data: &mut CalloopData,
+    log: Logger,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dh = data.display.handle();
+    let state = &mut data.state;
+
+    let (session, notifier) = AutoSession::new(log.clone()).ok_or("failed to acquire a session")?;
+    let seat_name = session.seat();
+
+    let udev_backend = UdevBackend::new(&seat_name, log.clone())?;
+
+    let mut libinput_context =
+        Libinput::new_with_udev::<LibinputSessionInterface<AutoSession>>(session.clone().into());
+    libinput_context.udev_assign_seat(&seat_name).unwrap();
+    let mut libinput_for_session = libinput_context.clone();
+    let libinput_backend = LibinputInputBackend::new(libinput_context, log.clone());
+
+    let handle = event_loop.handle();
+    let devices: DeviceMap = Rc::new(RefCell::new(HashMap::new()));
+    let paused: PausedFlag = Rc::new(Cell::new(false));
+
+    handle.insert_source(libinput_backend, |event, _, data| {
+        let state = &mut data.state;
+        // Tiled outputs sit side-by-side, so the output under the pointer's
+        // current position - not just whichever one mapped first - must be
+        // used, or relative motion can never carry the pointer past output 0.
+        if let Some(output) = state.output_under_pointer() {
+            state.process_input_event(event, &output);
+        }
+    })?;
+
+    // On VT-switch-away the session revokes DRM master and libinput access;
+    // on switch-back we reacquire both and force a full repaint so nothing
+    // renders against a stale, now-invalid device fd in between.
+    handle.insert_source(notifier, {
+        let devices = devices.clone();
+        let paused = paused.clone();
+        move |signal, _, data| match signal {
+            SessionSignal::PauseSession => {
+                paused.set(true);
+                libinput_for_session.suspend();
+                for device in devices.borrow_mut().values_mut() {
+                    device.drm.pause();
+                }
+            }
+            SessionSignal::ActivateSession => {
+                if libinput_for_session.resume().is_err() {
+                    slog::warn!(data.state.log, "failed to resume libinput after session activation");
+                }
+
+                let mut reactivated = Vec::new();
+                for (&device_id, device) in devices.borrow_mut().iter_mut() {
+                    if let Err(err) = device.drm.activate() {
+                        slog::warn!(data.state.log, "failed to reactivate drm device: {}", err);
+                        continue;
+                    }
+                    device.surfaces.values_mut().for_each(|s| s.surface.reset_buffers());
+                    reactivated.extend(device.surfaces.keys().map(|crtc| (device_id, *crtc)));
+                }
+                paused.set(false);
+
+                // Nothing will schedule a fresh vblank on its own, and this
+                // backend only renders on vblank, so force one render per CRTC
+                // now instead of leaving the screen blank.
+                for (device_id, crtc) in reactivated {
+                    render_surface(&devices, device_id, crtc, &mut data.state);
+                }
+            }
+        }
+    })?;
+
+    for (device_id, path) in udev_backend.device_list() {
+        add_device(
+            &handle,
+            &session,
+            &dh,
+            state,
+            device_id,
+            path.to_path_buf(),
+            &devices,
+            &paused,
+            &log,
+        );
+    }
+
+    handle.insert_source(udev_backend, {
+        let devices = devices.clone();
+        let session = session.clone();
+        let handle = handle.clone();
+        let paused = paused.clone();
+        let log = log.clone();
+        move |event, _, data| match event {
+            UdevEvent::Added { device_id, path } => {
+                let dh = data.display.handle();
+                add_device(
+                    &handle,
+                    &session,
+                    &dh,
+                    &mut data.state,
+                    device_id,
+                    path,
+                    &devices,
+                    &paused,
+                    &log,
+                );
+            }
+            UdevEvent::Changed { .. } => {}
+            UdevEvent::Removed { device_id } => {
+                devices.borrow_mut().remove(&device_id);
+            }
+        }
+    })?;
+
+    std::env::set_var("WAYLAND_DISPLAY", &state.socket_name);
+
+    Ok(())
+}
+
+fn add_device(
+    handle: &LoopHandle<'static, CalloopData>,
+    session: &AutoSession,
+    dh: &DisplayHandle,
+    state: &mut State,
+    device_id: DeviceId,
+    path: PathBuf,
+    devices: &DeviceMap,
+    paused: &PausedFlag,
+    log: &Logger,
+) {
+    let fd = match session.open(&path, OFlag::O_RDWR | OFlag::O_CLOEXEC | OFlag::O_NONBLOCK) {
+        Ok(fd) => fd,
+        Err(err) => {
+            slog::warn!(log, "failed to open drm device {:?}: {}", path, err);
+            return;
+        }
+    };
+    let file = unsafe { <std::fs::File as std::os::unix::io::FromRawFd>::from_raw_fd(fd) };
+
+    let (drm, drm_notifier) = match DrmDevice::new(file.try_clone().unwrap(), true, log.clone()) {
+        Ok(result) => result,
+        Err(err) => {
+            slog::warn!(log, "failed to init drm device {:?}: {}", path, err);
+            return;
+        }
+    };
+
+    let gbm = match GbmDevice::new(file) {
+        Ok(gbm) => gbm,
+        Err(err) => {
+            slog::warn!(log, "failed to init gbm device {:?}: {}", path, err);
+            return;
+        }
+    };
+
+    let egl = EGLDisplay::new(&gbm, log.clone()).expect("failed to create EGLDisplay");
+    let context = EGLContext::new(&egl, log.clone()).expect("failed to create EGLContext");
+    let renderer = unsafe { Gles2Renderer::new(context, log.clone()) }.expect("failed to create renderer");
+
+    let mut surfaces = HashMap::new();
+    let resources = drm.resource_handles().expect("failed to read drm resources");
+    let mut used_crtcs = HashSet::new();
+
+    for connector_handle in resources.connectors() {
+        let connector_info = drm.get_connector(*connector_handle).unwrap();
+        if connector_info.state() != ConnectorState::Connected {
+            continue;
+        }
+
+        let mode = connector_info
+            .modes()
+            .iter()
+            .find(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
+            .or_else(|| connector_info.modes().first());
+        let mode = match mode {
+            Some(mode) => *mode,
+            None => continue,
+        };
+
+        let possible_crtcs = connector_info
+            .current_encoder()
+            .and_then(|encoder| drm.get_encoder(encoder).ok())
+            .map(|encoder| encoder.possible_crtcs())
+            .unwrap_or_default();
+        let crtc_handle = match resources
+            .filter_crtcs(possible_crtcs)
+            .into_iter()
+            .find(|crtc| !used_crtcs.contains(crtc))
+        {
+            Some(crtc) => crtc,
+            None => continue,
+        };
+        used_crtcs.insert(crtc_handle);
+
+        let drm_surface = match drm.create_surface(crtc_handle, mode, &[*connector_handle]) {
+            Ok(surface) => surface,
+            Err(err) => {
+                slog::warn!(log, "failed to create drm surface: {}", err);
+                continue;
+            }
+        };
+        let surface = match GbmBufferedSurface::new(drm_surface, gbm.clone(), log.clone()) {
+            Ok(surface) => surface,
+            Err(err) => {
+                slog::warn!(log, "failed to init gbm surface: {}", err);
+                continue;
+            }
+        };
+
+        let (phys_w, phys_h) = connector_info.size().unwrap_or((0, 0));
+        let output = Output::new::<_>(
+            format!("{:?}", connector_handle),
+            PhysicalProperties {
+                size: (phys_w as i32, phys_h as i32).into(),
+                subpixel: wl_output::Subpixel::Unknown,
+                make: "Smithay".into(),
+                model: "episk".into(),
+            },
+            log.clone(),
+        );
+
+        let output_mode = OutputMode {
+            size: (mode.size().0 as i32, mode.size().1 as i32).into(),
+            refresh: (mode.vrefresh() as i32) * 1000,
+        };
+
+        // Tile outputs side-by-side instead of stacking every connector at the
+        // same origin, the way winit_backend only ever has to handle one.
+        let tiled_x: i32 = state
+            .space
+            .outputs()
+            .map(|output| state.space.output_geometry(output).unwrap().size.w)
+            .sum();
+
+        output.change_current_state(Some(output_mode), None, None, Some((tiled_x, 0).into()));
+        output.set_preferred(output_mode);
+
+        let _global = output.create_global::<State>(dh);
+        state.space.map_output(&output, (tiled_x, 0));
+
+        surfaces.insert(crtc_handle, OutputSurface { surface, output });
+    }
+
+    handle
+        .insert_source(drm_notifier, {
+            let devices = devices.clone();
+            let paused = paused.clone();
+            move |event, _, data| match event {
+                DrmEvent::VBlank(crtc) => {
+                    // Master can be revoked between the signal firing and this
+                    // event draining from calloop's queue; never touch the
+                    // device fd while paused.
+                    if !paused.get() {
+                        render_surface(&devices, device_id, crtc, &mut data.state);
+                    }
+                }
+                DrmEvent::Error(err) => slog::error!(data.state.log, "drm error: {}", err),
+            }
+        })
+        .expect("failed to register drm event source");
+
+    devices.borrow_mut().insert(
+        device_id,
+        Device {
+            drm,
+            gbm,
+            renderer,
+            surfaces,
+        },
+    );
+}
+
+fn render_surface(devices: &DeviceMap, device_id: DeviceId, crtc: crtc::Handle, state: &mut State) {
+    let mut devices = devices.borrow_mut();
+    let device = match devices.get_mut(&device_id) {
+        Some(device) => device,
+        None => return,
+    };
+    let output_surface = match device.surfaces.get_mut(&crtc) {
+        Some(surface) => surface,
+        None => return,
+    };
+
+    let dmabuf = match output_surface.surface.next_buffer() {
+        Ok((dmabuf, _age)) => dmabuf,
+        Err(err) => {
+            slog::warn!(state.log, "failed to get next buffer: {:?}", err);
+            return;
+        }
+    };
+
+    device.renderer.bind(dmabuf).expect("failed to bind buffer");
+
+    state
+        .space
+        .render_output::<Gles2Renderer, SurfaceTree>(
+            &mut device.renderer,
+            &output_surface.output,
+            0,
+            [0.1, 0.1, 0.1, 1.0],
+            &[],
+        )
+        .unwrap();
+
+    output_surface.surface.queue_buffer().expect("failed to queue buffer");
+
+    state.space.send_frames(state.start_time.elapsed().as_millis() as u32);
+}
@@ -0,0 +1,222 @@
+use slog::Logger;
+use smithay::{
+    desktop::{Kind, Window, WindowSurfaceType},
+    reexports::{calloop::LoopHandle, wayland_server::DisplayHandle},
+    utils::{Logical, Rectangle},
+    wayland::{
+        data_device::{clear_data_device_selection, request_data_device_client_selection, set_data_device_selection},
+        seat::Seat,
+    },
+    xwayland::{
+        xwm::{Reorder, ResizeEdge as X11ResizeEdge, X11Surface, XwmHandler, XwmId},
+        X11Wm, XWayland, XWaylandEvent,
+    },
+};
+
+use crate::{
+    grabs::{MoveSurfaceGrab, ResizeSurfaceGrab},
+    handlers::xdg_shell::check_grab,
+    CalloopData, State,
+};
+
+impl State {
+    /// Spawns the rootless XWayland server and wires its "ready" event up to an
+    /// `X11Wm` that maps X11 top-levels into the same `Space` the xdg windows
+    /// live in.
+    pub fn start_xwayland(&mut self, handle: &LoopHandle<'static, CalloopData>, log: Logger) {
+        let (xwayland, channel) = XWayland::new(log.clone(), &self.display_handle);
+
+        let log_ready = log.clone();
+        let loop_handle = handle.clone();
+        handle
+            .insert_source(channel, move |event, _, data| match event {
+                XWaylandEvent::Ready {
+                    connection,
+                    client,
+                    display,
+                    ..
+                } => {
+                    let wm = X11Wm::start_wm(
+                        loop_handle.clone(),
+                        data.display.handle(),
+                        connection,
+                        client,
+                        log_ready.clone(),
+                    )
+                    .expect("Failed to start the rootless X11 window manager");
+
+                    std::env::set_var("DISPLAY", format!(":{}", display));
+                    data.state.xwm = Some(wm);
+
+                    // Only spawn test clients once DISPLAY (and WAYLAND_DISPLAY,
+                    // set by the backend before this fires) are both in the
+                    // process environment; spawning any earlier hands clients a
+                    // window of time with no DISPLAY to connect to.
+                    std::process::Command::new("alacritty").spawn().ok();
+                }
+                XWaylandEvent::Exited => {
+                    data.state.xwm = None;
+                }
+            })
+            .expect("Failed to insert the XWayland event source");
+
+        xwayland
+            .start(handle.clone(), None, std::iter::empty::<(String, String)>(), |_| {})
+            .expect("Failed to start XWayland");
+
+        self.xwayland = Some(xwayland);
+    }
+
+    fn window_for_x11(&self, surface: &X11Surface) -> Option<Window> {
+        self.space
+            .windows()
+            .find(|window| matches!(window.toplevel(), Kind::X11(s) if s == surface))
+            .cloned()
+    }
+}
+
+impl XwmHandler for State {
+    fn xwm(&mut self) -> &mut X11Wm {
+        self.xwm.as_mut().expect("XwmHandler called before XWayland was ready")
+    }
+
+    fn new_window(&mut self, _xwm: XwmId, _window: X11Surface) {}
+    fn new_override_redirect_window(&mut self, _xwm: XwmId, _window: X11Surface) {}
+
+    fn map_window_request(&mut self, _xwm: XwmId, window: X11Surface) {
+        window.set_mapped(true).ok();
+
+        let location = window.geometry().loc;
+        let new_window = Window::new(Kind::X11(window));
+        self.space.map_window(&new_window, location, None, true);
+
+        let serial = smithay::wayland::SERIAL_COUNTER.next_serial();
+        self.focus_window(&new_window, serial);
+    }
+
+    fn mapped_override_redirect_window(&mut self, _xwm: XwmId, window: X11Surface) {
+        let location = window.geometry().loc;
+        let new_window = Window::new(Kind::X11(window));
+        self.space.map_window(&new_window, location, None, false);
+    }
+
+    fn unmapped_window(&mut self, _xwm: XwmId, window: X11Surface) {
+        if let Some(win) = self.window_for_x11(&window) {
+            self.space.unmap_window(&win);
+        }
+    }
+
+    fn destroyed_window(&mut self, _xwm: XwmId, _window: X11Surface) {}
+
+    fn configure_request(
+        &mut self,
+        _xwm: XwmId,
+        window: X11Surface,
+        x: Option<i32>,
+        y: Option<i32>,
+        w: Option<u32>,
+        h: Option<u32>,
+        _reorder: Option<Reorder>,
+    ) {
+        let mut geometry = window.geometry();
+        if let Some(x) = x {
+            geometry.loc.x = x;
+        }
+        if let Some(y) = y {
+            geometry.loc.y = y;
+        }
+        if let Some(w) = w {
+            geometry.size.w = w as i32;
+        }
+        if let Some(h) = h {
+            geometry.size.h = h as i32;
+        }
+        window.configure(geometry).ok();
+    }
+
+    fn configure_notify(
+        &mut self,
+        _xwm: XwmId,
+        window: X11Surface,
+        geometry: Rectangle<i32, Logical>,
+        _above: Option<u32>,
+    ) {
+        if let Some(win) = self.window_for_x11(&window) {
+            self.space.map_window(&win, geometry.loc, None, false);
+        }
+    }
+
+    fn resize_request(&mut self, _xwm: XwmId, window: X11Surface, seat: Seat<State>, serial: smithay::wayland::Serial, edges: X11ResizeEdge) {
+        let wl_surface = match window.wl_surface() {
+            Some(surface) => surface,
+            None => return,
+        };
+
+        if let Some(start_data) = check_grab(&seat, &wl_surface, serial) {
+            if let Some(win) = self.window_for_x11(&window) {
+                let pointer = seat.get_pointer().unwrap();
+                let initial_window_location = self.space.window_location(&win).unwrap();
+                let initial_window_size = win.geometry().size;
+
+                let grab = ResizeSurfaceGrab::start(
+                    start_data,
+                    win,
+                    edges.into(),
+                    Rectangle::from_loc_and_size(initial_window_location, initial_window_size),
+                );
+
+                pointer.set_grab(grab, serial, smithay::wayland::seat::Focus::Clear);
+            }
+        }
+    }
+
+    fn move_request(&mut self, _xwm: XwmId, window: X11Surface, seat: Seat<State>, serial: smithay::wayland::Serial) {
+        let wl_surface = match window.wl_surface() {
+            Some(surface) => surface,
+            None => return,
+        };
+
+        if let Some(start_data) = check_grab(&seat, &wl_surface, serial) {
+            if let Some(win) = self.window_for_x11(&window) {
+                let pointer = seat.get_pointer().unwrap();
+                let initial_window_location = self.space.window_location(&win).unwrap();
+
+                let grab = MoveSurfaceGrab {
+                    start_data,
+                    window: win,
+                    initial_window_location,
+                };
+
+                pointer.set_grab(grab, serial, smithay::wayland::seat::Focus::Clear);
+            }
+        }
+    }
+
+    fn allow_selection_access(&mut self, _xwm: XwmId, _selection: smithay::xwayland::xwm::SelectionTarget) -> bool {
+        true
+    }
+
+    fn send_selection(
+        &mut self,
+        _xwm: XwmId,
+        _selection: smithay::xwayland::xwm::SelectionTarget,
+        mime_type: String,
+        fd: std::os::unix::io::OwnedFd,
+    ) {
+        // Answer the X11 client's paste request by asking whichever Wayland
+        // client currently owns the selection to write `mime_type` into `fd`,
+        // the same fd-based path `wl_data_device` itself uses.
+        if let Err(err) = request_data_device_client_selection(&self.seat, mime_type, fd) {
+            slog::warn!(self.log, "failed to forward wayland selection to X11: {}", err);
+        }
+    }
+
+    fn new_selection(&mut self, _xwm: XwmId, selection: smithay::xwayland::xwm::SelectionTarget, mime_types: Vec<String>) {
+        if mime_types.is_empty() {
+            clear_data_device_selection(&self.display_handle, &self.seat);
+        } else {
+            let _ = selection;
+            set_data_device_selection(&self.display_handle, &self.seat, mime_types, ());
+        }
+    }
+}